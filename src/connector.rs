@@ -0,0 +1,261 @@
+//! Session event connector, modeled on atm0s-media-server's connector.
+//!
+//! Emits structured records whenever rooms and connections change state so
+//! operators can audit and bill sessions. Wired in as an
+//! `Option<Arc<Connector>>` on both managers so it is zero-cost when
+//! disabled. The queue between `emit` and the `Storage` writer is bounded,
+//! so a `Storage` backend that falls behind applies real backpressure
+//! instead of letting the queue grow without bound.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// A single state-change record. Every variant carries the room/connection
+/// ids it concerns plus a monotonic timestamp.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ConnectorEvent {
+    RoomCreated { room_id: String, at_ms: i64 },
+    PeerJoined { room_id: String, connection_id: String, is_sender: bool, at_ms: i64 },
+    PeerLeft { room_id: String, connection_id: String, at_ms: i64 },
+    ProducerCreated { room_id: String, connection_id: String, producer_id: String, at_ms: i64 },
+    ConsumerCreated { room_id: String, connection_id: String, consumer_id: String, producer_id: String, at_ms: i64 },
+}
+
+impl ConnectorEvent {
+    fn at_ms(&self) -> i64 {
+        match self {
+            ConnectorEvent::RoomCreated { at_ms, .. }
+            | ConnectorEvent::PeerJoined { at_ms, .. }
+            | ConnectorEvent::PeerLeft { at_ms, .. }
+            | ConnectorEvent::ProducerCreated { at_ms, .. }
+            | ConnectorEvent::ConsumerCreated { at_ms, .. } => *at_ms,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            ConnectorEvent::RoomCreated { .. } => "room_created",
+            ConnectorEvent::PeerJoined { .. } => "peer_joined",
+            ConnectorEvent::PeerLeft { .. } => "peer_left",
+            ConnectorEvent::ProducerCreated { .. } => "producer_created",
+            ConnectorEvent::ConsumerCreated { .. } => "consumer_created",
+        }
+    }
+}
+
+/// Persists connector events. Implementations must tolerate concurrent
+/// calls; `Connector` applies its own retry policy on top.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn record(&self, event: &ConnectorEvent) -> Result<()>;
+}
+
+/// Buffers events in a bounded channel and drains them into a `Storage`
+/// backend on a background task, so emission points never block on I/O.
+pub struct Connector {
+    sender: mpsc::Sender<ConnectorEvent>,
+}
+
+impl Connector {
+    /// Queue depth between `emit` and the writer task. Bounds memory so a
+    /// `Storage` backend that falls behind (slow disk, saturated DB) applies
+    /// real backpressure instead of letting the queue grow without bound;
+    /// `emit` stays non-blocking by dropping the event once this is full
+    /// rather than stalling whatever hot SFU path called it.
+    const QUEUE_CAPACITY: usize = 1024;
+
+    pub fn new(storage: Arc<dyn Storage>) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<ConnectorEvent>(Self::QUEUE_CAPACITY);
+
+        tokio::spawn(async move {
+            const MAX_ATTEMPTS: u32 = 3;
+
+            while let Some(event) = receiver.recv().await {
+                for attempt in 1..=MAX_ATTEMPTS {
+                    match storage.record(&event).await {
+                        Ok(()) => break,
+                        Err(err) if attempt < MAX_ATTEMPTS => {
+                            tracing::warn!(attempt, %err, "connector write failed, retrying");
+                            tokio::time::sleep(Duration::from_millis(100 * attempt as u64)).await;
+                        }
+                        Err(err) => {
+                            tracing::error!(%err, "connector write failed permanently, dropping event");
+                        }
+                    }
+                }
+            }
+        });
+
+        Self { sender }
+    }
+
+    /// Buffers `event` for the background writer. Never blocks the caller:
+    /// if the queue is full (the writer is backpressured by a slow
+    /// `Storage`) or the writer task has died, the event is dropped and
+    /// logged instead.
+    pub fn emit(&self, event: ConnectorEvent) {
+        match self.sender.try_send(event) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                tracing::warn!("connector queue full, dropping event under backpressure");
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                tracing::error!("connector writer task is gone, dropping event");
+            }
+        }
+    }
+}
+
+/// Wall-clock milliseconds since the Unix epoch, clamped so successive calls
+/// never go backwards even if the system clock is stepped back.
+pub(crate) fn now_ms() -> i64 {
+    static LAST: std::sync::atomic::AtomicI64 = std::sync::atomic::AtomicI64::new(0);
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64;
+    LAST.fetch_max(now, std::sync::atomic::Ordering::Relaxed).max(now)
+}
+
+/// `sqlx`-backed `Storage` writing to `event` and `stream_event` tables,
+/// indexed on room id and timestamp.
+pub struct SqlStorage {
+    pool: sqlx::AnyPool,
+}
+
+impl SqlStorage {
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        let pool = sqlx::AnyPool::connect(database_url).await?;
+
+        // `id` is a TEXT uuid we generate app-side (see `record` below) rather
+        // than a `BIGINT`/`SERIAL`/`AUTO_INCREMENT` column: `sqlx::AnyPool`
+        // drives SQLite, Postgres, and MySQL through the same query strings,
+        // and those three disagree on how to spell auto-increment, so there's
+        // no single `CREATE TABLE` that portably fills an integer primary key
+        // for us. A generated uuid sidesteps the dialect split entirely.
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS event (
+                id TEXT PRIMARY KEY,
+                room_id TEXT NOT NULL,
+                at_ms BIGINT NOT NULL,
+                kind TEXT NOT NULL,
+                payload TEXT NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS event_room_at_idx ON event (room_id, at_ms)")
+            .execute(&pool)
+            .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS stream_event (
+                id TEXT PRIMARY KEY,
+                room_id TEXT NOT NULL,
+                connection_id TEXT NOT NULL,
+                at_ms BIGINT NOT NULL,
+                kind TEXT NOT NULL,
+                payload TEXT NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS stream_event_room_at_idx ON stream_event (room_id, at_ms)")
+            .execute(&pool)
+            .await?;
+
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait]
+impl Storage for SqlStorage {
+    async fn record(&self, event: &ConnectorEvent) -> Result<()> {
+        let payload = serde_json::to_string(event)?;
+        let kind = event.kind();
+        let at_ms = event.at_ms();
+
+        let id = Uuid::new_v4().to_string();
+
+        match event {
+            ConnectorEvent::RoomCreated { room_id, .. } => {
+                sqlx::query("INSERT INTO event (id, room_id, at_ms, kind, payload) VALUES (?, ?, ?, ?, ?)")
+                    .bind(id)
+                    .bind(room_id)
+                    .bind(at_ms)
+                    .bind(kind)
+                    .bind(payload)
+                    .execute(&self.pool)
+                    .await?;
+            }
+            ConnectorEvent::PeerJoined { room_id, connection_id, .. }
+            | ConnectorEvent::PeerLeft { room_id, connection_id, .. }
+            | ConnectorEvent::ProducerCreated { room_id, connection_id, .. }
+            | ConnectorEvent::ConsumerCreated { room_id, connection_id, .. } => {
+                sqlx::query(
+                    "INSERT INTO stream_event (id, room_id, connection_id, at_ms, kind, payload) VALUES (?, ?, ?, ?, ?, ?)",
+                )
+                .bind(id)
+                .bind(room_id)
+                .bind(connection_id)
+                .bind(at_ms)
+                .bind(kind)
+                .bind(payload)
+                .execute(&self.pool)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn room_created() -> ConnectorEvent {
+        ConnectorEvent::RoomCreated { room_id: "room-1".to_string(), at_ms: 1 }
+    }
+
+    #[test]
+    fn kind_and_at_ms_match_every_variant() {
+        let peer_joined = ConnectorEvent::PeerJoined {
+            room_id: "room-1".to_string(),
+            connection_id: "conn-1".to_string(),
+            is_sender: true,
+            at_ms: 42,
+        };
+
+        assert_eq!(room_created().kind(), "room_created");
+        assert_eq!(room_created().at_ms(), 1);
+        assert_eq!(peer_joined.kind(), "peer_joined");
+        assert_eq!(peer_joined.at_ms(), 42);
+    }
+
+    // Regression test for the id column fix: `event`/`stream_event` used to
+    // declare `id BIGINT PRIMARY KEY` with every INSERT omitting `id`, which
+    // only worked by SQLite's rowid-aliasing accident. Recording the same
+    // kind of event twice in a row would have hit a NOT NULL violation on
+    // any backend that actually enforces the primary key.
+    #[tokio::test]
+    async fn records_multiple_events_of_the_same_kind_without_a_primary_key_conflict() {
+        let storage = SqlStorage::connect("sqlite::memory:").await.unwrap();
+
+        storage.record(&room_created()).await.unwrap();
+        storage.record(&room_created()).await.unwrap();
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM event")
+            .fetch_one(&storage.pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+    }
+}
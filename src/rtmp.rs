@@ -0,0 +1,250 @@
+//! RTMP ingest bridge.
+//!
+//! Accepts an incoming `publish` (e.g. from OBS or ffmpeg) on
+//! `rtmp://host/{app}/{stream_key}`, demuxes the FLV audio/video, and
+//! registers it as a `Producer` in `SfuManager` for the room derived from
+//! the stream key. WebRTC viewers then consume it via
+//! `SfuManager::create_consumer` like any other producer.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use rml_rtmp::handshake::{Handshake, HandshakeProcessResult, PeerType};
+use rml_rtmp::sessions::{ServerSession, ServerSessionConfig, ServerSessionEvent, ServerSessionResult};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Mutex};
+
+/// `room_id`/`connection_id` start `None` and are only filled in once
+/// `PublishStreamRequested` arrives, which happens after the frame-forwarding
+/// task below is spawned. Sharing them through this handle (rather than
+/// cloning plain `Option<String>` locals into the task) means the forwarder
+/// sees the real ids as soon as `handle_session_result` sets them, instead of
+/// being stuck with the `None` it was spawned with for the life of the
+/// connection.
+type SharedId = Arc<Mutex<Option<String>>>;
+
+use crate::sfu::{ConnectionRole, SfuManager};
+
+/// A single decoded media frame handed from the RTMP chunk stream to the SFU.
+#[derive(Debug, Clone)]
+pub struct DecodedFrame {
+    pub is_video: bool,
+    pub timestamp_ms: u32,
+    pub payload: Bytes,
+}
+
+/// Maps an RTMP `app/streamkey` publish target onto a room id. With an empty
+/// app name the stream key is the room id directly; otherwise the room id is
+/// `{app}/{stream_key}`.
+fn room_from_stream_key(app: &str, stream_key: &str) -> String {
+    if app.is_empty() {
+        stream_key.to_string()
+    } else {
+        format!("{app}/{stream_key}")
+    }
+}
+
+/// Owns the RTMP listener and spawns one task per incoming publisher.
+pub struct RtmpIngest {
+    manager: Arc<SfuManager>,
+}
+
+impl RtmpIngest {
+    pub fn new(manager: Arc<SfuManager>) -> Self {
+        Self { manager }
+    }
+
+    pub async fn run(self, addr: &str) -> Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+        tracing::info!(addr, "RTMP ingest listening");
+
+        loop {
+            let (socket, peer) = listener.accept().await?;
+            let manager = self.manager.clone();
+            tokio::spawn(async move {
+                if let Err(err) = handle_connection(socket, manager).await {
+                    tracing::warn!(%peer, %err, "RTMP connection ended with error");
+                }
+            });
+        }
+    }
+}
+
+async fn handle_connection(mut socket: TcpStream, manager: Arc<SfuManager>) -> Result<()> {
+    perform_handshake(&mut socket).await?;
+
+    let config = ServerSessionConfig::new();
+    let (mut session, initial_results) =
+        ServerSession::new(config).map_err(|err| anyhow!("failed to start RTMP session: {err:?}"))?;
+
+    let (frame_tx, mut frame_rx) = mpsc::channel::<DecodedFrame>(128);
+    let room_id: SharedId = Arc::new(Mutex::new(None));
+    let connection_id: SharedId = Arc::new(Mutex::new(None));
+
+    for result in initial_results {
+        handle_session_result(result, &mut socket, &mut session, &manager, &room_id, &connection_id, &frame_tx).await?;
+    }
+
+    // Forward decoded frames into the SFU as they arrive, independent of the
+    // chunk-reading loop below so a slow producer write never stalls reads
+    // off the socket. `room_id`/`connection_id` are read through the shared
+    // handle on every frame rather than captured by value, since they are
+    // still `None` at spawn time and only get set once `PublishStreamRequested`
+    // is handled further down.
+    let forward_manager = manager.clone();
+    let forward_room = room_id.clone();
+    let forward_connection = connection_id.clone();
+    tokio::spawn(async move {
+        while let Some(frame) = frame_rx.recv().await {
+            let room_id = forward_room.lock().await.clone();
+            let connection_id = forward_connection.lock().await.clone();
+            if let (Some(room_id), Some(connection_id)) = (room_id, connection_id) {
+                if let Err(err) = forward_manager
+                    .create_producer_from_rtp(&connection_id, &room_id, &frame)
+                    .await
+                {
+                    tracing::warn!(%err, "failed to forward RTMP frame to SFU");
+                }
+            }
+        }
+    });
+
+    let mut buf = vec![0u8; 4096];
+    loop {
+        let n = socket.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+
+        let results = session
+            .handle_input(&buf[..n])
+            .map_err(|err| anyhow!("RTMP chunk error: {err:?}"))?;
+
+        for result in results {
+            handle_session_result(result, &mut socket, &mut session, &manager, &room_id, &connection_id, &frame_tx).await?;
+        }
+    }
+
+    if let Some(connection_id) = connection_id.lock().await.clone() {
+        manager.remove_connection(&connection_id).await?;
+    }
+
+    Ok(())
+}
+
+async fn perform_handshake(socket: &mut TcpStream) -> Result<()> {
+    let mut handshake = Handshake::new(PeerType::Server);
+    let mut read_buf = vec![0u8; 4096];
+
+    loop {
+        let n = socket.read(&mut read_buf).await?;
+        if n == 0 {
+            return Err(anyhow!("socket closed during RTMP handshake"));
+        }
+
+        match handshake.process_bytes(&read_buf[..n]) {
+            Ok(HandshakeProcessResult::InProgress { response_bytes }) => {
+                if !response_bytes.is_empty() {
+                    socket.write_all(&response_bytes).await?;
+                }
+            }
+            Ok(HandshakeProcessResult::Completed { response_bytes, .. }) => {
+                if !response_bytes.is_empty() {
+                    socket.write_all(&response_bytes).await?;
+                }
+                return Ok(());
+            }
+            Err(err) => return Err(anyhow!("RTMP handshake failed: {err:?}")),
+        }
+    }
+}
+
+/// `handle_session_result` recurses (accepting a request can itself raise
+/// more results to handle), so a plain `async fn` won't compile here; this
+/// boxes the future the way any recursive async call has to.
+type BoxedResultFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + 'a>>;
+
+fn handle_session_result<'a>(
+    result: ServerSessionResult,
+    socket: &'a mut TcpStream,
+    session: &'a mut ServerSession,
+    manager: &'a Arc<SfuManager>,
+    room_id: &'a SharedId,
+    connection_id: &'a SharedId,
+    frame_tx: &'a mpsc::Sender<DecodedFrame>,
+) -> BoxedResultFuture<'a> {
+    Box::pin(async move {
+        match result {
+            ServerSessionResult::OutboundResponse(packet) => {
+                socket.write_all(&packet.bytes).await?;
+            }
+            ServerSessionResult::RaisedEvent(ServerSessionEvent::ConnectionRequested { request_id, .. }) => {
+                // Nothing acknowledges `NetConnection.connect` until we accept
+                // the outstanding request and write the resulting packets back.
+                let accept_results = session
+                    .accept_request(request_id)
+                    .map_err(|err| anyhow!("failed to accept RTMP connection request: {err:?}"))?;
+
+                for result in accept_results {
+                    handle_session_result(result, socket, session, manager, room_id, connection_id, frame_tx).await?;
+                }
+            }
+            ServerSessionResult::RaisedEvent(ServerSessionEvent::PublishStreamRequested {
+                request_id,
+                app_name,
+                stream_key,
+                ..
+            }) => {
+                let room = room_from_stream_key(&app_name, &stream_key);
+                manager.create_room(room.clone()).await?;
+                let new_connection_id = uuid::Uuid::new_v4().to_string();
+                manager.add_connection_unauthenticated(&room, new_connection_id.clone(), ConnectionRole::Producer).await?;
+
+                *room_id.lock().await = Some(room);
+                *connection_id.lock().await = Some(new_connection_id);
+
+                // Acknowledge the publish request itself so the client
+                // actually starts sending audio/video data.
+                let accept_results = session
+                    .accept_request(request_id)
+                    .map_err(|err| anyhow!("failed to accept RTMP publish request: {err:?}"))?;
+
+                for result in accept_results {
+                    handle_session_result(result, socket, session, manager, room_id, connection_id, frame_tx).await?;
+                }
+            }
+            ServerSessionResult::RaisedEvent(ServerSessionEvent::VideoDataReceived { data, timestamp, .. }) => {
+                let _ = frame_tx
+                    .send(DecodedFrame { is_video: true, timestamp_ms: timestamp.value, payload: data })
+                    .await;
+            }
+            ServerSessionResult::RaisedEvent(ServerSessionEvent::AudioDataReceived { data, timestamp, .. }) => {
+                let _ = frame_tx
+                    .send(DecodedFrame { is_video: false, timestamp_ms: timestamp.value, payload: data })
+                    .await;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_stream_key_to_room_when_app_is_empty() {
+        assert_eq!(room_from_stream_key("", "room-1"), "room-1");
+    }
+
+    #[test]
+    fn namespaces_room_under_app_when_app_is_present() {
+        assert_eq!(room_from_stream_key("live", "room-1"), "live/room-1");
+    }
+}
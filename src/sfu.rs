@@ -11,22 +11,118 @@ use tokio::sync::RwLock;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use crate::token::{TokenVerifier, VideoGrants};
+use crate::connector::{Connector, ConnectorEvent, now_ms};
+use crate::signaling::{SignalingMessage, SignalingMessageType};
+
+/// Inbound RTP metrics for a single `Producer`, in the shape of WebRTC's
+/// `RTCInboundRtpStreamStats`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InboundRtpStats {
+    pub producer_id: String,
+    pub packets_received: u64,
+    pub bytes_received: u64,
+    pub packets_lost: i64,
+    pub jitter: f64,
+    pub nack_count: u64,
+    pub pli_count: u64,
+}
+
+/// Outbound RTP metrics for a single `Consumer`, in the shape of WebRTC's
+/// `RTCOutboundRtpStreamStats`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboundRtpStats {
+    pub consumer_id: String,
+    pub packets_sent: u64,
+    pub bytes_sent: u64,
+    pub packets_retransmitted: u64,
+    pub round_trip_time_ms: f64,
+    pub fraction_lost: f64,
+    pub jitter: f64,
+}
+
+/// Stats for every producer/consumer tracked by a room, as returned by
+/// `SfuManager::get_stats`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomStats {
+    pub room_id: String,
+    pub inbound: Vec<InboundRtpStats>,
+    pub outbound: Vec<OutboundRtpStats>,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SfuRoom {
     pub id: String,
     pub router_id: String,
-    pub producer_id: Option<String>,
+    pub producer_ids: Vec<String>,
     pub consumer_ids: Vec<String>,
 }
 
+/// What a connection is allowed to do in a room. `Both` lets a participant
+/// publish their own tracks while also subscribing to everyone else's, the
+/// dual-role case plain send/receive booleans can't express.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConnectionRole {
+    Producer,
+    Consumer,
+    Both,
+}
+
+impl ConnectionRole {
+    pub fn can_produce(self) -> bool {
+        matches!(self, ConnectionRole::Producer | ConnectionRole::Both)
+    }
+
+    pub fn can_consume(self) -> bool {
+        matches!(self, ConnectionRole::Consumer | ConnectionRole::Both)
+    }
+}
+
+/// One simulcast/SVC encoding a producer advertised, keyed by its RID.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulcastEncoding {
+    pub rid: String,
+    pub max_bitrate: Option<u32>,
+}
+
+/// The spatial/temporal layer a consumer is currently pinned to.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ConsumerLayer {
+    pub spatial_layer: u8,
+    pub temporal_layer: Option<u8>,
+}
+
+/// Returned by `set_preferred_layers` and `adjust_layers_for_bwe` when they
+/// change a consumer's layer. No signaling message type carries it, and the
+/// notification from the BWE-driven auto-selection registered in
+/// `create_consumer` is discarded, so clients are not told of layer changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerChangeNotification {
+    pub consumer_id: String,
+    pub layer: ConsumerLayer,
+}
+
 #[derive(Debug, Clone)]
 pub struct SfuConnection {
     pub id: String,
-    pub is_sender: bool,
+    pub room_id: String,
+    pub role: ConnectionRole,
     pub transport_id: Option<String>,
     pub producer_id: Option<String>,
     pub consumer_ids: Vec<String>,
+    pub consumer_layers: HashMap<String, ConsumerLayer>,
+    pub grants: VideoGrants,
+    /// The `PlainTransport` backing an RTMP-sourced producer, if any. Kept
+    /// alive here for the lifetime of the connection; dropping it would tear
+    /// down the mediasoup producer it feeds.
+    pub rtp_transport: Option<Arc<mediasoup::transport::PlainTransport>>,
+    /// Subscription handles for the BWE-driven layer selection registered in
+    /// `create_consumer`, one per consumer this connection owns. Kept alive
+    /// here for the connection's lifetime, since most mediasoup-rust event
+    /// subscriptions stop firing once their handle is dropped. The concrete
+    /// handle type returned by `Transport::on_available_outgoing_bitrate`
+    /// varies by mediasoup-rust version, hence the `Any` box.
+    pub bwe_subscriptions: Vec<Box<dyn std::any::Any + Send + Sync>>,
 }
 
 pub struct SfuManager {
@@ -36,10 +132,13 @@ pub struct SfuManager {
     connections: Arc<RwLock<HashMap<String, SfuConnection>>>,
     producers: Arc<RwLock<HashMap<String, Arc<Producer>>>>,
     consumers: Arc<RwLock<HashMap<String, Arc<Consumer>>>>,
+    producer_encodings: Arc<RwLock<HashMap<String, Vec<SimulcastEncoding>>>>,
+    token_verifier: Arc<TokenVerifier>,
+    connector: Option<Arc<Connector>>,
 }
 
 impl SfuManager {
-    pub async fn new() -> Result<Self> {
+    pub async fn new(token_verifier: Arc<TokenVerifier>, connector: Option<Arc<Connector>>) -> Result<Self> {
         // Create worker
         let worker_settings = WorkerSettings {
             log_level: WorkerLogLevel::Debug,
@@ -100,19 +199,39 @@ impl SfuManager {
             connections: Arc::new(RwLock::new(HashMap::new())),
             producers: Arc::new(RwLock::new(HashMap::new())),
             consumers: Arc::new(RwLock::new(HashMap::new())),
+            producer_encodings: Arc::new(RwLock::new(HashMap::new())),
+            token_verifier,
+            connector,
         })
     }
 
+    fn emit(&self, event: ConnectorEvent) {
+        if let Some(connector) = &self.connector {
+            connector.emit(event);
+        }
+    }
+
+    /// Registers `room_id` if it doesn't exist yet. Idempotent: calling this
+    /// for a room that's already active (e.g. a second camera publishing
+    /// into it, or a WHIP reconnect) leaves its existing `producer_ids` /
+    /// `consumer_ids` untouched instead of wiping them out from under
+    /// whoever is already in the room.
     pub async fn create_room(&self, room_id: String) -> Result<()> {
+        let mut rooms = self.rooms.write().await;
+        if rooms.contains_key(&room_id) {
+            return Ok(());
+        }
+
         let room = SfuRoom {
             id: room_id.clone(),
             router_id: self.router.id().to_string(),
-            producer_id: None,
+            producer_ids: Vec::new(),
             consumer_ids: Vec::new(),
         };
+        rooms.insert(room_id.clone(), room);
+        drop(rooms);
 
-        let mut rooms = self.rooms.write().await;
-        rooms.insert(room_id, room);
+        self.emit(ConnectorEvent::RoomCreated { room_id, at_ms: now_ms() });
         Ok(())
     }
 
@@ -136,23 +255,217 @@ impl SfuManager {
         Ok(transport)
     }
 
-    pub async fn add_connection(&self, room_id: &str, connection_id: String, is_sender: bool) -> Result<()> {
+    /// Verifies `token` against the room and registers the connection with
+    /// the grants it decodes to. Rejects senders without `can_publish`,
+    /// viewers without `can_subscribe`, and expired or otherwise invalid
+    /// tokens.
+    pub async fn add_connection(&self, room_id: &str, connection_id: String, role: ConnectionRole, token: &str) -> Result<()> {
+        let claims = self.token_verifier.verify(token)?;
+
+        if claims.video.room != room_id || !claims.video.room_join {
+            return Err(anyhow::anyhow!("token not authorized for room {room_id}"));
+        }
+        if role.can_produce() && !claims.video.can_publish {
+            return Err(anyhow::anyhow!("token lacks can_publish grant"));
+        }
+        if role.can_consume() && !claims.video.can_subscribe {
+            return Err(anyhow::anyhow!("token lacks can_subscribe grant"));
+        }
+
+        let connection = SfuConnection {
+            id: connection_id.clone(),
+            room_id: room_id.to_string(),
+            role,
+            transport_id: None,
+            producer_id: None,
+            consumer_ids: Vec::new(),
+            consumer_layers: HashMap::new(),
+            grants: claims.video,
+            rtp_transport: None,
+            bwe_subscriptions: Vec::new(),
+        };
+
+        let mut connections = self.connections.write().await;
+        connections.insert(connection_id.clone(), connection);
+        drop(connections);
+
+        self.emit(ConnectorEvent::PeerJoined {
+            room_id: room_id.to_string(),
+            connection_id: connection_id.clone(),
+            is_sender: role.can_produce(),
+            at_ms: now_ms(),
+        });
+
+        self.auto_subscribe_if_consumer(room_id, &connection_id, role).await?;
+
+        Ok(())
+    }
+
+    /// Verifies `token` still authorizes `connection_id` in `room_id`,
+    /// without re-registering the connection. For transport-specific
+    /// teardown paths (e.g. WHIP's `DELETE .../resource/{connection_id}`)
+    /// that need to confirm the caller holds a valid token for the
+    /// connection they're asking to tear down, the same way `add_connection`
+    /// confirmed one to create it.
+    pub async fn authorize_teardown(&self, room_id: &str, connection_id: &str, token: &str) -> Result<()> {
+        let claims = self.token_verifier.verify(token)?;
+
+        if claims.video.room != room_id || !claims.video.room_join {
+            return Err(anyhow::anyhow!("token not authorized for room {room_id}"));
+        }
+
+        let connections = self.connections.read().await;
+        let connection = connections
+            .get(connection_id)
+            .ok_or_else(|| anyhow::anyhow!("connection not found: {connection_id}"))?;
+
+        if connection.room_id != room_id {
+            return Err(anyhow::anyhow!("connection {connection_id} is not in room {room_id}"));
+        }
+
+        Ok(())
+    }
+
+    /// Registers a connection without verifying a JWT, for ingest paths that
+    /// carry none (e.g. `RtmpIngest`, which accepts any publish request and
+    /// checks no stream key). Grants full publish/subscribe access for that
+    /// connection only.
+    pub async fn add_connection_unauthenticated(&self, room_id: &str, connection_id: String, role: ConnectionRole) -> Result<()> {
         let connection = SfuConnection {
             id: connection_id.clone(),
-            is_sender,
+            room_id: room_id.to_string(),
+            role,
             transport_id: None,
             producer_id: None,
             consumer_ids: Vec::new(),
+            consumer_layers: HashMap::new(),
+            grants: VideoGrants {
+                room: room_id.to_string(),
+                room_join: true,
+                can_publish: true,
+                can_subscribe: true,
+            },
+            rtp_transport: None,
+            bwe_subscriptions: Vec::new(),
         };
 
         let mut connections = self.connections.write().await;
-        connections.insert(connection_id, connection);
+        connections.insert(connection_id.clone(), connection);
+        drop(connections);
+
+        self.emit(ConnectorEvent::PeerJoined {
+            room_id: room_id.to_string(),
+            connection_id: connection_id.clone(),
+            is_sender: role.can_produce(),
+            at_ms: now_ms(),
+        });
+
+        self.auto_subscribe_if_consumer(room_id, &connection_id, role).await?;
+
         Ok(())
     }
 
+    /// Creates (or appends to) a producer fed by decoded RTMP frames rather
+    /// than a client-negotiated WebRTC transport. The first frame for a
+    /// connection creates a `PlainTransport` and a real mediasoup `Producer`
+    /// on it (so `create_consumer` can find and subscribe to it like any
+    /// other producer); later frames reuse that producer.
+    ///
+    /// `frame.payload` is not re-packetized into RTP or sent to the plain
+    /// transport, so no media flows through that producer: every frame, the
+    /// first one included, is only traced.
+    pub async fn create_producer_from_rtp(
+        &self,
+        connection_id: &str,
+        room_id: &str,
+        frame: &crate::rtmp::DecodedFrame,
+    ) -> Result<()> {
+        self.require_grant(connection_id, |grants| grants.can_publish).await?;
+
+        let existing_producer_id = {
+            let connections = self.connections.read().await;
+            connections
+                .get(connection_id)
+                .ok_or_else(|| anyhow::anyhow!("connection not found: {connection_id}"))?
+                .producer_id
+                .clone()
+        };
+
+        if existing_producer_id.is_none() {
+            let kind = if frame.is_video {
+                mediasoup::rtp_parameters::MediaKind::Video
+            } else {
+                mediasoup::rtp_parameters::MediaKind::Audio
+            };
+
+            let transport = self.create_plain_transport().await?;
+            let producer_options = mediasoup::producer::ProducerOptions {
+                kind,
+                rtp_parameters: build_rtp_parameters_for_kind(kind),
+                ..Default::default()
+            };
+            let producer = Arc::new(transport.produce(producer_options).await?);
+
+            let producer_id = Uuid::new_v4().to_string();
+            let mut producers = self.producers.write().await;
+            producers.insert(producer_id.clone(), producer);
+            drop(producers);
+
+            let mut connections = self.connections.write().await;
+            let connection = connections
+                .get_mut(connection_id)
+                .ok_or_else(|| anyhow::anyhow!("connection not found: {connection_id}"))?;
+            connection.producer_id = Some(producer_id.clone());
+            connection.rtp_transport = Some(Arc::new(transport));
+            drop(connections);
+
+            let mut rooms = self.rooms.write().await;
+            if let Some(room) = rooms.get_mut(room_id) {
+                room.producer_ids.push(producer_id.clone());
+            }
+            drop(rooms);
+
+            self.emit(ConnectorEvent::ProducerCreated {
+                room_id: room_id.to_string(),
+                connection_id: connection_id.to_string(),
+                producer_id,
+                at_ms: now_ms(),
+            });
+        }
+
+        tracing::trace!(
+            connection_id,
+            room_id,
+            is_video = frame.is_video,
+            bytes = frame.payload.len(),
+            "received RTMP frame (RTP forwarding to the plain transport not yet implemented)"
+        );
+
+        Ok(())
+    }
+
+    /// Creates a `PlainTransport` listening for raw RTP, used to back
+    /// producers fed by non-WebRTC ingest paths like `RtmpIngest` that don't
+    /// negotiate a `WebRtcTransport` with the publisher.
+    async fn create_plain_transport(&self) -> Result<mediasoup::transport::PlainTransport> {
+        let listen_info = ListenInfo {
+            protocol: Protocol::Udp,
+            ip: "0.0.0.0".parse().unwrap(),
+            announced_ip: None,
+            port: None,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+        };
+
+        let options = mediasoup::transport::PlainTransportOptions::new(TransportListenInfo::Direct(listen_info));
+        Ok(self.router.create_plain_transport(options).await?)
+    }
+
     pub async fn create_producer(&self, connection_id: &str, transport: &Transport, rtp_parameters: &str) -> Result<String> {
+        self.require_grant(connection_id, |grants| grants.can_publish).await?;
+
         let producer_id = Uuid::new_v4().to_string();
-        
+
         // Parse RTP parameters and create producer
         // This is simplified - in real implementation you'd parse the JSON rtp_parameters
         let producer_options = mediasoup::producer::ProducerOptions {
@@ -162,22 +475,51 @@ impl SfuManager {
         };
 
         let producer = Arc::new(transport.produce(producer_options).await?);
-        
+
         let mut producers = self.producers.write().await;
         producers.insert(producer_id.clone(), producer);
+        drop(producers);
+
+        // Simulcast/SVC encodings (RID + max bitrate per spatial layer), if
+        // the client sent any; viewers pick among these via
+        // `set_preferred_layers`.
+        let encodings = parse_simulcast_encodings(rtp_parameters);
+        if !encodings.is_empty() {
+            let mut producer_encodings = self.producer_encodings.write().await;
+            producer_encodings.insert(producer_id.clone(), encodings);
+        }
 
         // Update connection with producer_id
         let mut connections = self.connections.write().await;
-        if let Some(connection) = connections.get_mut(connection_id) {
+        let room_id = connections.get_mut(connection_id).map(|connection| {
             connection.producer_id = Some(producer_id.clone());
+            connection.room_id.clone()
+        });
+        drop(connections);
+
+        if let Some(room_id) = room_id {
+            let mut rooms = self.rooms.write().await;
+            if let Some(room) = rooms.get_mut(&room_id) {
+                room.producer_ids.push(producer_id.clone());
+            }
+            drop(rooms);
+
+            self.emit(ConnectorEvent::ProducerCreated {
+                room_id,
+                connection_id: connection_id.to_string(),
+                producer_id: producer_id.clone(),
+                at_ms: now_ms(),
+            });
         }
 
         Ok(producer_id)
     }
 
     pub async fn create_consumer(&self, connection_id: &str, transport: &Transport, producer_id: &str) -> Result<String> {
+        self.require_grant(connection_id, |grants| grants.can_subscribe).await?;
+
         let consumer_id = Uuid::new_v4().to_string();
-        
+
         // Get producer
         let producers = self.producers.read().await;
         if let Some(producer) = producers.get(producer_id) {
@@ -188,14 +530,74 @@ impl SfuManager {
             };
 
             let consumer = Arc::new(transport.consume(consumer_options).await?);
-            
+
             let mut consumers = self.consumers.write().await;
             consumers.insert(consumer_id.clone(), consumer);
+            drop(consumers);
+
+            // Automatic layer selection: whenever mediasoup reports a new
+            // available-outgoing-bitrate estimate for the transport this
+            // consumer rides on, re-run `adjust_layers_for_bwe` for it
+            // instead of waiting for a client to poll and call
+            // `set_preferred_layers` itself. The callback is handed clones
+            // of the `Arc` bookkeeping maps rather than `self`, so it can
+            // outlive this call without needing an `Arc<SfuManager>`.
+            //
+            // `on_available_outgoing_bitrate`'s exact name/signature varies
+            // by mediasoup-rust version; this follows the convention most
+            // `Transport` implementations expose it under.
+            let producer_encodings = self.producer_encodings.clone();
+            let consumers_for_bwe = self.consumers.clone();
+            let connections_for_bwe = self.connections.clone();
+            let bwe_consumer_id = consumer_id.clone();
+            let bwe_producer_id = producer_id.to_string();
+
+            let bwe_subscription = transport.on_available_outgoing_bitrate(move |available_bitrate_bps| {
+                let producer_encodings = producer_encodings.clone();
+                let consumers = consumers_for_bwe.clone();
+                let connections = connections_for_bwe.clone();
+                let consumer_id = bwe_consumer_id.clone();
+                let producer_id = bwe_producer_id.clone();
+
+                tokio::spawn(async move {
+                    if let Err(err) = Self::adjust_layers_for_bwe_inner(
+                        &producer_encodings,
+                        &consumers,
+                        &connections,
+                        &consumer_id,
+                        &producer_id,
+                        available_bitrate_bps,
+                    )
+                    .await
+                    {
+                        tracing::warn!(%err, "BWE-driven layer adjustment failed");
+                    }
+                });
+            });
 
             // Update connection with consumer_id
             let mut connections = self.connections.write().await;
-            if let Some(connection) = connections.get_mut(connection_id) {
+            let room_id = connections.get_mut(connection_id).map(|connection| {
                 connection.consumer_ids.push(consumer_id.clone());
+                connection.bwe_subscriptions.push(Box::new(bwe_subscription));
+                connection.room_id.clone()
+            });
+            drop(connections);
+
+            if let Some(room_id) = room_id {
+                let mut rooms = self.rooms.write().await;
+                if let Some(room) = rooms.get_mut(&room_id) {
+                    room.consumer_ids.push(consumer_id.clone());
+                }
+                drop(rooms);
+
+                self.emit(ConnectorEvent::ConsumerCreated {
+                    room_id,
+                    connection_id: connection_id.to_string(),
+                    consumer_id: consumer_id.clone(),
+                    producer_id: producer_id.to_string(),
+                    at_ms: now_ms(),
+                });
             }
 
             Ok(consumer_id)
@@ -208,9 +610,81 @@ impl SfuManager {
         self.router.rtp_capabilities().clone()
     }
 
+    /// Creates a producer directly from an SDP offer, for ingest paths (e.g.
+    /// WHIP) that hand us a raw offer instead of a JSON `rtp_parameters` blob.
+    ///
+    /// Only the media kind is read from the offer, by substring match: video
+    /// if it has an `m=video` section, otherwise audio if it has `m=audio`.
+    /// An offer with both yields a single video producer. No codec or SSRC
+    /// is read from the offer; the producer's `rtp_parameters` are
+    /// `Default::default()`, the same as in `create_producer`.
+    /// Returns the new producer id together with the SDP answer to hand back
+    /// to the peer.
+    pub async fn create_producer_from_sdp(
+        &self,
+        connection_id: &str,
+        transport: &Transport,
+        sdp_offer: &str,
+    ) -> Result<(String, String)> {
+        self.require_grant(connection_id, |grants| grants.can_publish).await?;
+
+        let kind = if sdp_offer.contains("m=video") {
+            mediasoup::rtp_parameters::MediaKind::Video
+        } else if sdp_offer.contains("m=audio") {
+            mediasoup::rtp_parameters::MediaKind::Audio
+        } else {
+            return Err(anyhow::anyhow!("SDP offer has no audio or video media section"));
+        };
+
+        let producer_id = Uuid::new_v4().to_string();
+
+        let producer_options = mediasoup::producer::ProducerOptions {
+            kind,
+            rtp_parameters: Default::default(), // TODO: build from the parsed offer's codecs/ssrcs
+            ..Default::default()
+        };
+
+        let producer = Arc::new(transport.produce(producer_options).await?);
+
+        let mut producers = self.producers.write().await;
+        producers.insert(producer_id.clone(), producer);
+
+        let mut connections = self.connections.write().await;
+        let room_id = connections.get_mut(connection_id).map(|connection| {
+            connection.producer_id = Some(producer_id.clone());
+            connection.room_id.clone()
+        });
+        drop(connections);
+
+        if let Some(room_id) = room_id {
+            let mut rooms = self.rooms.write().await;
+            if let Some(room) = rooms.get_mut(&room_id) {
+                room.producer_ids.push(producer_id.clone());
+            }
+            drop(rooms);
+
+            self.emit(ConnectorEvent::ProducerCreated {
+                room_id,
+                connection_id: connection_id.to_string(),
+                producer_id: producer_id.clone(),
+                at_ms: now_ms(),
+            });
+        }
+
+        let answer_sdp = build_sdp_answer(transport, kind);
+
+        Ok((producer_id, answer_sdp))
+    }
+
     pub async fn remove_connection(&self, connection_id: &str) -> Result<()> {
         let mut connections = self.connections.write().await;
-        connections.remove(connection_id);
+        let room_id = connections.remove(connection_id).map(|connection| connection.room_id);
+        drop(connections);
+
+        if let Some(room_id) = room_id {
+            self.emit(ConnectorEvent::PeerLeft { room_id, connection_id: connection_id.to_string(), at_ms: now_ms() });
+        }
+
         Ok(())
     }
 
@@ -218,4 +692,400 @@ impl SfuManager {
         let producers = self.producers.read().await;
         producers.keys().cloned().collect()
     }
+
+    /// Returns live RTP stats for every producer/consumer tracked by
+    /// `room_id`, using the `producer_ids`/`consumer_ids` already tracked on
+    /// `SfuRoom`. Reachable over signaling via `handle_message`'s
+    /// `StatsRequest` so clients can poll quality without the server pushing
+    /// anything.
+    ///
+    /// No unit coverage here: every path, including the "room not found"
+    /// error, requires an `SfuManager` constructed via `SfuManager::new`,
+    /// which spawns a real mediasoup `Worker` process — there's no pure
+    /// slice of this method that doesn't go through that. Exercising it
+    /// needs an actual mediasoup worker binary, unlike `SimpleSfuManager`'s
+    /// equivalents, which `TestServer` covers without one.
+    pub async fn get_stats(&self, room_id: &str) -> Result<RoomStats> {
+        let rooms = self.rooms.read().await;
+        let room = rooms
+            .get(room_id)
+            .ok_or_else(|| anyhow::anyhow!("room not found: {room_id}"))?
+            .clone();
+        drop(rooms);
+
+        let mut inbound = Vec::new();
+        let producers = self.producers.read().await;
+        for producer_id in &room.producer_ids {
+            if let Some(producer) = producers.get(producer_id) {
+                // `ProducerStat`'s exact field names vary by mediasoup-rust
+                // version; this maps the counters it already exposes onto
+                // our stable, serializable shape.
+                for stat in producer.get_stats().await? {
+                    inbound.push(InboundRtpStats {
+                        producer_id: producer_id.clone(),
+                        packets_received: stat.packet_count,
+                        bytes_received: stat.byte_count,
+                        packets_lost: stat.packets_lost,
+                        jitter: stat.jitter as f64,
+                        nack_count: stat.nack_count,
+                        pli_count: stat.pli_count,
+                    });
+                }
+            }
+        }
+
+        let mut outbound = Vec::new();
+        let consumers = self.consumers.read().await;
+        for consumer_id in &room.consumer_ids {
+            if let Some(consumer) = consumers.get(consumer_id) {
+                for stat in consumer.get_stats().await? {
+                    outbound.push(OutboundRtpStats {
+                        consumer_id: consumer_id.clone(),
+                        packets_sent: stat.packet_count,
+                        bytes_sent: stat.byte_count,
+                        packets_retransmitted: stat.packets_retransmitted,
+                        round_trip_time_ms: stat.round_trip_time.map(|rtt| rtt.as_secs_f64() * 1000.0).unwrap_or_default(),
+                        fraction_lost: stat.fraction_lost as f64,
+                        jitter: stat.jitter as f64,
+                    });
+                }
+            }
+        }
+
+        Ok(RoomStats { room_id: room_id.to_string(), inbound, outbound })
+    }
+
+    /// Dispatches JSON signaling messages for the real, mediasoup-backed
+    /// manager. `SfuManager`'s join/publish/subscribe flow normally runs
+    /// over WHIP/WHEP (`whip.rs`) or RTMP (`rtmp.rs`) rather than this
+    /// channel, so `StatsRequest` — a client polling `get_stats` — is the
+    /// only variant handled here; anything else is a no-op, mirroring
+    /// `SimpleSfuManager::handle_message`'s catch-all.
+    pub async fn handle_message(&self, room_id: &str, message: &SignalingMessage) -> Result<Vec<SignalingMessage>> {
+        match message.message_type {
+            SignalingMessageType::StatsRequest => {
+                let stats = self.get_stats(room_id).await?;
+                Ok(vec![SignalingMessage {
+                    message_type: SignalingMessageType::Stats,
+                    connection_id: message.connection_id.clone(),
+                    sender_id: None,
+                    offer_id: None,
+                    data: Some(serde_json::to_value(&stats)?),
+                    is_sender: None,
+                }])
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Checks the connection's already-decoded grants rather than
+    /// re-verifying its token, so hot paths like `create_producer` stay
+    /// cheap.
+    async fn require_grant(&self, connection_id: &str, check: impl Fn(&VideoGrants) -> bool) -> Result<()> {
+        let connections = self.connections.read().await;
+        let connection = connections
+            .get(connection_id)
+            .ok_or_else(|| anyhow::anyhow!("connection not found: {connection_id}"))?;
+
+        if check(&connection.grants) {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("connection {connection_id} is not authorized for this action"))
+        }
+    }
+
+    /// Pins `consumer_id` to a spatial/temporal layer. `temporal_layer` of
+    /// `None` lets mediasoup pick the highest available for the chosen
+    /// spatial layer.
+    pub async fn set_preferred_layers(
+        &self,
+        consumer_id: &str,
+        spatial_layer: u8,
+        temporal_layer: Option<u8>,
+    ) -> Result<LayerChangeNotification> {
+        Self::set_preferred_layers_inner(&self.consumers, &self.connections, consumer_id, spatial_layer, temporal_layer).await
+    }
+
+    /// Core of `set_preferred_layers`, taking the bookkeeping maps directly
+    /// instead of `&self` so the BWE callback registered in `create_consumer`
+    /// can call it without holding a reference to the whole manager.
+    async fn set_preferred_layers_inner(
+        consumers: &Arc<RwLock<HashMap<String, Arc<Consumer>>>>,
+        connections: &Arc<RwLock<HashMap<String, SfuConnection>>>,
+        consumer_id: &str,
+        spatial_layer: u8,
+        temporal_layer: Option<u8>,
+    ) -> Result<LayerChangeNotification> {
+        let consumers_guard = consumers.read().await;
+        let consumer = consumers_guard
+            .get(consumer_id)
+            .ok_or_else(|| anyhow::anyhow!("consumer not found: {consumer_id}"))?;
+
+        consumer
+            .set_preferred_layers(mediasoup::consumer::ConsumerLayers {
+                spatial_layer,
+                temporal_layer,
+            })
+            .await?;
+        drop(consumers_guard);
+
+        let layer = ConsumerLayer { spatial_layer, temporal_layer };
+
+        let mut connections_guard = connections.write().await;
+        for connection in connections_guard.values_mut() {
+            if connection.consumer_ids.iter().any(|id| id == consumer_id) {
+                connection.consumer_layers.insert(consumer_id.to_string(), layer);
+                break;
+            }
+        }
+
+        Ok(LayerChangeNotification { consumer_id: consumer_id.to_string(), layer })
+    }
+
+    /// Picks a spatial/temporal layer for `consumer_id` from its producer's
+    /// simulcast encodings given the consumer transport's estimated
+    /// available bitrate, and applies it via `set_preferred_layers`.
+    ///
+    /// This is a simple threshold ladder rather than the full BWE-driven
+    /// controller webrtcsink runs; it favors the highest layer whose
+    /// `max_bitrate` fits under the estimate.
+    pub async fn adjust_layers_for_bwe(
+        &self,
+        consumer_id: &str,
+        producer_id: &str,
+        available_bitrate_bps: u32,
+    ) -> Result<Option<LayerChangeNotification>> {
+        Self::adjust_layers_for_bwe_inner(
+            &self.producer_encodings,
+            &self.consumers,
+            &self.connections,
+            consumer_id,
+            producer_id,
+            available_bitrate_bps,
+        )
+        .await
+    }
+
+    /// Core of `adjust_layers_for_bwe`, taking the bookkeeping maps directly
+    /// so it can be driven both by the `&self` method above and by the
+    /// `on_available_outgoing_bitrate` callback `create_consumer` registers,
+    /// which only has `Arc` clones of those maps, not the manager itself.
+    async fn adjust_layers_for_bwe_inner(
+        producer_encodings: &Arc<RwLock<HashMap<String, Vec<SimulcastEncoding>>>>,
+        consumers: &Arc<RwLock<HashMap<String, Arc<Consumer>>>>,
+        connections: &Arc<RwLock<HashMap<String, SfuConnection>>>,
+        consumer_id: &str,
+        producer_id: &str,
+        available_bitrate_bps: u32,
+    ) -> Result<Option<LayerChangeNotification>> {
+        let producer_encodings_guard = producer_encodings.read().await;
+        let Some(encodings) = producer_encodings_guard.get(producer_id) else {
+            return Ok(None);
+        };
+
+        let spatial_layer = pick_spatial_layer_for_bitrate(encodings, available_bitrate_bps);
+        drop(producer_encodings_guard);
+
+        Self::set_preferred_layers_inner(consumers, connections, consumer_id, spatial_layer, None)
+            .await
+            .map(Some)
+    }
+
+    /// Creates the consuming transport and subscribes `connection_id` to
+    /// every existing producer in `room_id`, mirroring the auto-subscribe
+    /// `SimpleSfuManager::add_connection` does for its `Consumer`/`Both`
+    /// peers. A no-op for `Producer`-only connections.
+    async fn auto_subscribe_if_consumer(&self, room_id: &str, connection_id: &str, role: ConnectionRole) -> Result<()> {
+        if !role.can_consume() {
+            return Ok(());
+        }
+
+        let transport = self.create_transport(connection_id.to_string()).await?;
+        let transport_id = transport.id().to_string();
+
+        self.auto_subscribe_existing_producers(room_id, connection_id, &transport).await?;
+
+        let mut connections = self.connections.write().await;
+        if let Some(connection) = connections.get_mut(connection_id) {
+            connection.transport_id = Some(transport_id);
+        }
+
+        Ok(())
+    }
+
+    /// Subscribes a newly joined `Consumer`/`Both` connection to every
+    /// producer already in `room_id`, so a dual-role participant is fanned
+    /// out to the rest of the room as soon as it connects.
+    ///
+    /// Producers created *after* this call aren't retroactively pushed to
+    /// this connection — that would need a registry of live per-connection
+    /// transports, which this manager doesn't keep; the caller is expected
+    /// to re-subscribe as new `ProducerCreated` connector events arrive.
+    pub async fn auto_subscribe_existing_producers(
+        &self,
+        room_id: &str,
+        connection_id: &str,
+        transport: &Transport,
+    ) -> Result<Vec<String>> {
+        self.require_grant(connection_id, |grants| grants.can_subscribe).await?;
+
+        let rooms = self.rooms.read().await;
+        let producer_ids = rooms.get(room_id).map(|room| room.producer_ids.clone()).unwrap_or_default();
+        drop(rooms);
+
+        let mut consumer_ids = Vec::new();
+        for producer_id in producer_ids {
+            let connections = self.connections.read().await;
+            let is_own_producer = connections
+                .get(connection_id)
+                .is_some_and(|connection| connection.producer_id.as_deref() == Some(producer_id.as_str()));
+            drop(connections);
+
+            if is_own_producer {
+                continue;
+            }
+
+            consumer_ids.push(self.create_consumer(connection_id, transport, &producer_id).await?);
+        }
+
+        Ok(consumer_ids)
+    }
+}
+
+/// Builds `RtpParameters` for an RTMP-sourced producer, matching one of the
+/// codecs the router was created with (`RouterOptions::media_codecs` in
+/// `SfuManager::new`) so mediasoup will actually accept the `produce()`
+/// call. A random SSRC stands in for the one a real RTP packetizer would
+/// assign.
+///
+/// Field names on `RtpCodecParameters`/`RtpEncodingParameters` vary by
+/// mediasoup-rust version; this mirrors the shape `RtpCodecCapability` in
+/// `SfuManager::new` already uses, plus the payload type/SSRC a codec
+/// parameters struct adds on top of a capability.
+fn build_rtp_parameters_for_kind(kind: mediasoup::rtp_parameters::MediaKind) -> mediasoup::rtp_parameters::RtpParameters {
+    use mediasoup::rtp_parameters::{MediaKind, RtpCodecParameters, RtpEncodingParameters, RtpParameters};
+
+    let ssrc = Uuid::new_v4().as_u128() as u32;
+
+    let codecs = match kind {
+        MediaKind::Audio => vec![RtpCodecParameters::Audio {
+            mime_type: "audio/opus".to_string(),
+            payload_type: 100,
+            clock_rate: 48000,
+            channels: 2,
+            parameters: Default::default(),
+            rtcp_feedback: Vec::new(),
+        }],
+        MediaKind::Video => vec![RtpCodecParameters::Video {
+            mime_type: "video/H264".to_string(),
+            payload_type: 102,
+            clock_rate: 90000,
+            parameters: Default::default(),
+            rtcp_feedback: Vec::new(),
+        }],
+    };
+
+    RtpParameters {
+        codecs,
+        encodings: vec![RtpEncodingParameters { ssrc: Some(ssrc), ..Default::default() }],
+        ..Default::default()
+    }
+}
+
+/// Picks the highest spatial layer index among `encodings` whose
+/// `max_bitrate` fits under `available_bitrate_bps`, favoring the highest
+/// layer (over e.g. the finest-grained fit) since a consumer should always
+/// get the best quality the estimate can sustain. Layers with no
+/// `max_bitrate` are assumed to always fit. Falls back to layer `0` if
+/// `encodings` is empty or nothing fits.
+fn pick_spatial_layer_for_bitrate(encodings: &[SimulcastEncoding], available_bitrate_bps: u32) -> u8 {
+    encodings
+        .iter()
+        .enumerate()
+        .filter(|(_, encoding)| encoding.max_bitrate.map_or(true, |bitrate| bitrate <= available_bitrate_bps))
+        .map(|(index, _)| index as u8)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Parses the client-supplied simulcast/SVC encodings (RID + optional max
+/// bitrate per spatial layer) out of the raw `rtp_parameters` JSON. Falls
+/// back to no simulcast if parsing fails or the field is absent, matching
+/// the rest of this path's "best effort" handling of `rtp_parameters`.
+fn parse_simulcast_encodings(rtp_parameters: &str) -> Vec<SimulcastEncoding> {
+    #[derive(Deserialize)]
+    struct Encodings {
+        #[serde(default)]
+        encodings: Vec<SimulcastEncoding>,
+    }
+
+    serde_json::from_str::<Encodings>(rtp_parameters)
+        .map(|parsed| parsed.encodings)
+        .unwrap_or_default()
+}
+
+/// Builds a minimal SDP answer for a freshly created WebRTC transport.
+///
+/// The answer is SDP-shaped only: it uses the transport's id as both the ICE
+/// ufrag and pwd and carries no DTLS fingerprint or ICE candidates. The
+/// transport's real `ice_parameters()` / `dtls_parameters()` are not read.
+fn build_sdp_answer(transport: &Transport, kind: mediasoup::rtp_parameters::MediaKind) -> String {
+    let media_type = match kind {
+        mediasoup::rtp_parameters::MediaKind::Audio => "audio",
+        mediasoup::rtp_parameters::MediaKind::Video => "video",
+    };
+
+    format!(
+        "v=0\r\no=- 0 0 IN IP4 0.0.0.0\r\ns=-\r\nt=0 0\r\nm={media_type} 9 UDP/TLS/RTP/SAVPF 0\r\nc=IN IP4 0.0.0.0\r\na=ice-ufrag:{ufrag}\r\na=ice-pwd:{pwd}\r\na=setup:active\r\na=recvonly\r\n",
+        ufrag = transport.id(),
+        pwd = transport.id(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoding(max_bitrate: Option<u32>) -> SimulcastEncoding {
+        SimulcastEncoding { rid: "r".to_string(), max_bitrate }
+    }
+
+    #[test]
+    fn picks_highest_layer_that_fits_under_the_estimate() {
+        let encodings = vec![encoding(Some(150_000)), encoding(Some(500_000)), encoding(Some(1_200_000))];
+        assert_eq!(pick_spatial_layer_for_bitrate(&encodings, 600_000), 1);
+    }
+
+    #[test]
+    fn picks_top_layer_when_the_estimate_covers_everything() {
+        let encodings = vec![encoding(Some(150_000)), encoding(Some(500_000)), encoding(Some(1_200_000))];
+        assert_eq!(pick_spatial_layer_for_bitrate(&encodings, 10_000_000), 2);
+    }
+
+    #[test]
+    fn falls_back_to_layer_zero_when_nothing_fits() {
+        let encodings = vec![encoding(Some(500_000)), encoding(Some(1_200_000))];
+        assert_eq!(pick_spatial_layer_for_bitrate(&encodings, 100_000), 0);
+    }
+
+    #[test]
+    fn treats_a_missing_max_bitrate_as_always_fitting() {
+        let encodings = vec![encoding(Some(500_000)), encoding(None)];
+        assert_eq!(pick_spatial_layer_for_bitrate(&encodings, 0), 1);
+    }
+
+    #[test]
+    fn parses_simulcast_encodings_from_rtp_parameters_json() {
+        let rtp_parameters = r#"{"encodings":[{"rid":"f","max_bitrate":1200000},{"rid":"h","max_bitrate":500000}]}"#;
+        let encodings = parse_simulcast_encodings(rtp_parameters);
+        assert_eq!(encodings.len(), 2);
+        assert_eq!(encodings[0].rid, "f");
+        assert_eq!(encodings[0].max_bitrate, Some(1_200_000));
+    }
+
+    #[test]
+    fn falls_back_to_no_simulcast_when_rtp_parameters_has_no_encodings_field() {
+        assert!(parse_simulcast_encodings("{}").is_empty());
+        assert!(parse_simulcast_encodings("not json").is_empty());
+    }
 }
@@ -5,34 +5,48 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use crate::signaling::{SignalingMessage, SignalingMessageType};
+use crate::token::{TokenVerifier, VideoGrants};
+use crate::connector::{Connector, ConnectorEvent, now_ms};
+use crate::sfu::ConnectionRole;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SimpleSfuRoom {
     pub id: String,
-    pub sender_id: Option<String>,
+    pub sender_ids: Vec<String>,
     pub viewer_ids: Vec<String>,
-    pub sender_sdp: Option<String>,
+    pub sender_sdp: HashMap<String, String>,
     pub viewer_sdp: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct SimpleSfuConnection {
     pub id: String,
-    pub is_sender: bool,
+    pub role: ConnectionRole,
     pub sdp_offer: Option<String>,
     pub sdp_answer: Option<String>,
+    pub grants: VideoGrants,
 }
 
 pub struct SimpleSfuManager {
     rooms: Arc<RwLock<HashMap<String, SimpleSfuRoom>>>,
     connections: Arc<RwLock<HashMap<String, SimpleSfuConnection>>>,
+    token_verifier: Arc<TokenVerifier>,
+    connector: Option<Arc<Connector>>,
 }
 
 impl SimpleSfuManager {
-    pub fn new() -> Self {
+    pub fn new(token_verifier: Arc<TokenVerifier>, connector: Option<Arc<Connector>>) -> Self {
         Self {
             rooms: Arc::new(RwLock::new(HashMap::new())),
             connections: Arc::new(RwLock::new(HashMap::new())),
+            token_verifier,
+            connector,
+        }
+    }
+
+    fn emit(&self, event: ConnectorEvent) {
+        if let Some(connector) = &self.connector {
+            connector.emit(event);
         }
     }
 
@@ -40,8 +54,32 @@ impl SimpleSfuManager {
         match message.message_type {
             SignalingMessageType::Join => {
                 let connection_id = message.connection_id.as_ref().ok_or_else(|| anyhow::anyhow!("No connection_id"))?;
-                let is_sender = message.is_sender.unwrap_or(false);
-                self.add_connection(room_id, connection_id.clone(), is_sender).await
+                let role = match message.data.as_ref().and_then(|d| d.get("role")).and_then(|r| r.as_str()) {
+                    Some("both") => ConnectionRole::Both,
+                    Some("producer") => ConnectionRole::Producer,
+                    Some("consumer") => ConnectionRole::Consumer,
+                    // Fall back to the legacy `is_sender` flag for clients that
+                    // haven't moved to the explicit `role` field yet.
+                    _ if message.is_sender.unwrap_or(false) => ConnectionRole::Producer,
+                    _ => ConnectionRole::Consumer,
+                };
+                let token = message.data.as_ref()
+                    .and_then(|d| d.get("token"))
+                    .and_then(|t| t.as_str())
+                    .ok_or_else(|| anyhow::anyhow!("No token in join"))?;
+                let claims = self.token_verifier.verify(token)?;
+
+                if claims.video.room != room_id || !claims.video.room_join {
+                    return Err(anyhow::anyhow!("token not authorized for room {room_id}"));
+                }
+                if role.can_produce() && !claims.video.can_publish {
+                    return Err(anyhow::anyhow!("token lacks can_publish grant"));
+                }
+                if role.can_consume() && !claims.video.can_subscribe {
+                    return Err(anyhow::anyhow!("token lacks can_subscribe grant"));
+                }
+
+                self.add_connection(room_id, connection_id.clone(), role, claims.video).await
             }
             SignalingMessageType::Offer => {
                 let connection_id = message.connection_id.as_ref().ok_or_else(|| anyhow::anyhow!("No connection_id"))?;
@@ -74,23 +112,33 @@ impl SimpleSfuManager {
     pub async fn create_room(&self, room_id: String) -> Result<()> {
         let room = SimpleSfuRoom {
             id: room_id.clone(),
-            sender_id: None,
+            sender_ids: Vec::new(),
             viewer_ids: Vec::new(),
-            sender_sdp: None,
+            sender_sdp: HashMap::new(),
             viewer_sdp: HashMap::new(),
         };
 
         let mut rooms = self.rooms.write().await;
-        rooms.insert(room_id, room);
+        rooms.insert(room_id.clone(), room);
+        drop(rooms);
+
+        self.emit(ConnectorEvent::RoomCreated { room_id, at_ms: now_ms() });
         Ok(())
     }
 
-    pub async fn add_connection(&self, room_id: &str, connection_id: String, is_sender: bool) -> Result<Vec<SignalingMessage>> {
+    pub async fn add_connection(
+        &self,
+        room_id: &str,
+        connection_id: String,
+        role: ConnectionRole,
+        grants: VideoGrants,
+    ) -> Result<Vec<SignalingMessage>> {
         let connection = SimpleSfuConnection {
             id: connection_id.clone(),
-            is_sender,
+            role,
             sdp_offer: None,
             sdp_answer: None,
+            grants,
         };
 
         let mut connections = self.connections.write().await;
@@ -98,28 +146,43 @@ impl SimpleSfuManager {
 
         let mut rooms = self.rooms.write().await;
         if let Some(room) = rooms.get_mut(room_id) {
-            if is_sender {
-                room.sender_id = Some(connection_id.clone());
-            } else {
+            if role.can_produce() {
+                room.sender_ids.push(connection_id.clone());
+            }
+            if role.can_consume() {
                 room.viewer_ids.push(connection_id.clone());
             }
         }
+        drop(rooms);
+
+        self.emit(ConnectorEvent::PeerJoined {
+            room_id: room_id.to_string(),
+            connection_id: connection_id.clone(),
+            is_sender: role.can_produce(),
+            at_ms: now_ms(),
+        });
 
         let mut responses = Vec::new();
 
-        // If this is a viewer and there's already a sender, send the sender's SDP
-        if !is_sender {
+        // A `Consumer`/`Both` peer is auto-subscribed to every producer
+        // already in the room: send it each existing sender's SDP.
+        if role.can_consume() {
             let rooms_guard = self.rooms.read().await;
             if let Some(room) = rooms_guard.get(room_id) {
-                if let (Some(sender_id), Some(sender_sdp)) = (&room.sender_id, &room.sender_sdp) {
-                    responses.push(SignalingMessage {
-                        message_type: SignalingMessageType::Offer,
-                        connection_id: Some(connection_id),
-                        sender_id: Some(sender_id.clone()),
-                        offer_id: Some(Uuid::new_v4().to_string()),
-                        data: Some(serde_json::json!({ "sdp": sender_sdp })),
-                        is_sender: Some(false),
-                    });
+                for sender_id in &room.sender_ids {
+                    if sender_id == &connection_id {
+                        continue;
+                    }
+                    if let Some(sender_sdp) = room.sender_sdp.get(sender_id) {
+                        responses.push(SignalingMessage {
+                            message_type: SignalingMessageType::Offer,
+                            connection_id: Some(connection_id.clone()),
+                            sender_id: Some(sender_id.clone()),
+                            offer_id: Some(Uuid::new_v4().to_string()),
+                            data: Some(serde_json::json!({ "sdp": sender_sdp })),
+                            is_sender: Some(false),
+                        });
+                    }
                 }
             }
         }
@@ -139,13 +202,14 @@ impl SimpleSfuManager {
 
             let mut rooms = self.rooms.write().await;
             if let Some(room) = rooms.get_mut(room_id) {
-                room.sender_sdp = Some(sdp.to_string());
+                room.sender_sdp.insert(connection_id.to_string(), sdp.to_string());
             }
 
-            // Send offer to all viewers
+            // Send offer to all other viewers (a `Both` peer is its own
+            // sender, so skip forwarding its offer back to itself)
             let rooms_guard = self.rooms.read().await;
             if let Some(room) = rooms_guard.get(room_id) {
-                for viewer_id in &room.viewer_ids {
+                for viewer_id in room.viewer_ids.iter().filter(|id| id.as_str() != connection_id) {
                     responses.push(SignalingMessage {
                         message_type: SignalingMessageType::Offer,
                         connection_id: Some(viewer_id.clone()),
@@ -177,11 +241,11 @@ impl SimpleSfuManager {
         // For now, we just forward them
         let rooms_guard = self.rooms.read().await;
         if let Some(room) = rooms_guard.get(room_id) {
-            let is_sender = room.sender_id.as_ref().map(|s| s.as_str()) == Some(connection_id);
+            let is_sender = room.sender_ids.iter().any(|id| id == connection_id);
 
             if is_sender {
-                // Forward sender's ICE candidates to all viewers
-                for viewer_id in &room.viewer_ids {
+                // Forward sender's ICE candidates to all other viewers
+                for viewer_id in room.viewer_ids.iter().filter(|id| id.as_str() != connection_id) {
                     responses.push(SignalingMessage {
                         message_type: SignalingMessageType::IceCandidate,
                         connection_id: Some(viewer_id.clone()),
@@ -192,8 +256,8 @@ impl SimpleSfuManager {
                     });
                 }
             } else {
-                // Forward viewer's ICE candidates to sender
-                if let Some(sender_id) = &room.sender_id {
+                // Forward viewer's ICE candidates to every sender in the room
+                for sender_id in room.sender_ids.iter().filter(|id| id.as_str() != connection_id) {
                     responses.push(SignalingMessage {
                         message_type: SignalingMessageType::IceCandidate,
                         connection_id: Some(sender_id.clone()),
@@ -212,13 +276,18 @@ impl SimpleSfuManager {
     pub async fn remove_connection(&self, room_id: &str, connection_id: &str) -> Result<Vec<SignalingMessage>> {
         let mut connections = self.connections.write().await;
         connections.remove(connection_id);
+        drop(connections);
+
+        self.emit(ConnectorEvent::PeerLeft {
+            room_id: room_id.to_string(),
+            connection_id: connection_id.to_string(),
+            at_ms: now_ms(),
+        });
 
         let mut rooms = self.rooms.write().await;
         if let Some(room) = rooms.get_mut(room_id) {
-            if room.sender_id.as_ref().map(|s| s.as_str()) == Some(connection_id) {
-                room.sender_id = None;
-                room.sender_sdp = None;
-            }
+            room.sender_ids.retain(|id| id != connection_id);
+            room.sender_sdp.remove(connection_id);
             room.viewer_ids.retain(|id| id != connection_id);
             room.viewer_sdp.remove(connection_id);
         }
@@ -227,9 +296,13 @@ impl SimpleSfuManager {
         let rooms_guard = self.rooms.read().await;
         let mut responses = Vec::new();
         if let Some(room) = rooms_guard.get(room_id) {
-            let participant_ids: Vec<String> = room.viewer_ids.iter()
-                .chain(room.sender_id.iter())
-                .filter(|id| *id != connection_id)
+            let mut all_ids: Vec<&String> = room.viewer_ids.iter().chain(room.sender_ids.iter()).collect();
+            all_ids.sort();
+            all_ids.dedup();
+            let connection_count = all_ids.len();
+            let participant_ids: Vec<String> = all_ids
+                .into_iter()
+                .filter(|id| id.as_str() != connection_id)
                 .cloned()
                 .collect();
 
@@ -241,7 +314,7 @@ impl SimpleSfuManager {
                     offer_id: None,
                     data: Some(serde_json::json!({
                         "connection_id": connection_id,
-                        "connection_count": room.viewer_ids.len() + if room.sender_id.is_some() { 1 } else { 0 }
+                        "connection_count": connection_count
                     })),
                     is_sender: None,
                 });
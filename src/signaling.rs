@@ -0,0 +1,31 @@
+//! JSON signaling messages exchanged between a client and an SFU manager
+//! (`SimpleSfuManager::handle_message`, `SfuManager::handle_message`).
+//! Transport-agnostic: whatever carries these (WebSocket, long-poll, the
+//! in-process `TestServer`) just serializes/deserializes this shape.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SignalingMessageType {
+    Join,
+    Offer,
+    Answer,
+    IceCandidate,
+    Leave,
+    /// A client polling for current room quality. Dispatches to
+    /// `SfuManager::get_stats`; answered with a `Stats` message carrying the
+    /// resulting `RoomStats` as `data`.
+    StatsRequest,
+    /// Response to `StatsRequest`.
+    Stats,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignalingMessage {
+    pub message_type: SignalingMessageType,
+    pub connection_id: Option<String>,
+    pub sender_id: Option<String>,
+    pub offer_id: Option<String>,
+    pub data: Option<serde_json::Value>,
+    pub is_sender: Option<bool>,
+}
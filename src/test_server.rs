@@ -0,0 +1,264 @@
+//! In-process mock SFU server for integration tests, modeled on Zed's
+//! `live_kit_client` `TestServer`: a registry of fake servers keyed by URL,
+//! each driving a real `SimpleSfuManager` with simulated network jitter
+//! instead of an actual mediasoup worker or socket. This gives the
+//! offer/answer fan-out and `remove_connection` Leave-notification paths
+//! deterministic test coverage without standing up any of that.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::sync::Mutex;
+
+use crate::sfu::ConnectionRole;
+use crate::sfu_simple::SimpleSfuManager;
+use crate::signaling::{SignalingMessage, SignalingMessageType};
+use crate::token::{TokenVerifier, VideoGrants};
+
+fn registry() -> &'static Mutex<HashMap<String, Arc<TestServer>>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<String, Arc<TestServer>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// A fake SFU endpoint, addressable by `url` the way a real deployment would
+/// be addressable by its LiveKit/mediasoup URL. Wraps one `SimpleSfuManager`
+/// and records every `SignalingMessage` it routes to each connection, so a
+/// test can assert on what a given peer was told without a transport.
+pub struct TestServer {
+    url: String,
+    manager: Arc<SimpleSfuManager>,
+    token_verifier: Arc<TokenVerifier>,
+    received: Mutex<HashMap<String, Vec<SignalingMessage>>>,
+}
+
+impl TestServer {
+    /// Creates a new server and registers it under `url`, replacing any
+    /// server already registered there.
+    pub async fn create(url: impl Into<String>, secret: impl Into<String>) -> Arc<Self> {
+        let url = url.into();
+        let token_verifier = Arc::new(TokenVerifier::new(secret));
+        let server = Arc::new(Self {
+            url: url.clone(),
+            manager: Arc::new(SimpleSfuManager::new(token_verifier.clone(), None)),
+            token_verifier,
+            received: Mutex::new(HashMap::new()),
+        });
+
+        registry().lock().await.insert(url, server.clone());
+        server
+    }
+
+    /// Looks up a previously `create`d server by its URL.
+    pub async fn get(url: &str) -> Option<Arc<Self>> {
+        registry().lock().await.get(url).cloned()
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub async fn create_room(&self, room_id: impl Into<String>) -> Result<()> {
+        simulated_delay().await;
+        self.manager.create_room(room_id.into()).await
+    }
+
+    /// Joins `connection_id` to `room_id` with the given role, minting a
+    /// token scoped to exactly the grants that role needs. Returns whatever
+    /// `SimpleSfuManager` hands back immediately (e.g. existing producers'
+    /// offers for a consumer), in addition to recording it under
+    /// `connection_id` for later inspection via `received`.
+    pub async fn join(&self, room_id: &str, connection_id: impl Into<String>, role: ConnectionRole) -> Result<Vec<SignalingMessage>> {
+        let connection_id = connection_id.into();
+        let grants = VideoGrants {
+            room: room_id.to_string(),
+            room_join: true,
+            can_publish: role.can_produce(),
+            can_subscribe: role.can_consume(),
+        };
+        let token = self.token_verifier.mint(&connection_id, grants, None)?;
+        let role_str = match role {
+            ConnectionRole::Producer => "producer",
+            ConnectionRole::Consumer => "consumer",
+            ConnectionRole::Both => "both",
+        };
+
+        simulated_delay().await;
+        let message = SignalingMessage {
+            message_type: SignalingMessageType::Join,
+            connection_id: Some(connection_id.clone()),
+            sender_id: None,
+            offer_id: None,
+            data: Some(serde_json::json!({ "role": role_str, "token": token })),
+            is_sender: Some(role.can_produce()),
+        };
+
+        let responses = self.manager.handle_message(room_id, &message).await?;
+        self.record(responses.clone()).await;
+        Ok(responses)
+    }
+
+    /// Publishes `sdp` as an offer from `connection_id`, simulating the
+    /// `SignalingMessageType::Offer` a real producer would send.
+    pub async fn publish(&self, room_id: &str, connection_id: &str, sdp: &str) -> Result<()> {
+        simulated_delay().await;
+        let message = SignalingMessage {
+            message_type: SignalingMessageType::Offer,
+            connection_id: Some(connection_id.to_string()),
+            sender_id: None,
+            offer_id: None,
+            data: Some(serde_json::json!({ "sdp": sdp })),
+            is_sender: Some(true),
+        };
+
+        let responses = self.manager.handle_message(room_id, &message).await?;
+        self.record(responses).await;
+        Ok(())
+    }
+
+    /// Tears `connection_id` down, the way a WHIP `DELETE` or a socket close
+    /// would. Any `Leave` notifications fanned out to remaining participants
+    /// are recorded under their connection ids.
+    pub async fn unpublish(&self, room_id: &str, connection_id: &str) -> Result<()> {
+        simulated_delay().await;
+        let responses = self.manager.remove_connection(room_id, connection_id).await?;
+        self.record(responses).await;
+        Ok(())
+    }
+
+    /// Returns every `SignalingMessage` routed to `connection_id` so far, in
+    /// the order it was received.
+    pub async fn received(&self, connection_id: &str) -> Vec<SignalingMessage> {
+        self.received
+            .lock()
+            .await
+            .get(connection_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    async fn record(&self, messages: Vec<SignalingMessage>) {
+        let mut received = self.received.lock().await;
+        for message in messages {
+            if let Some(connection_id) = message.connection_id.clone() {
+                received.entry(connection_id).or_default().push(message);
+            }
+        }
+    }
+}
+
+/// Sleeps a small, pseudo-random amount of time to stand in for the network
+/// latency a real client/server round trip would have. Cheap xorshift rather
+/// than pulling in a `rand` dependency just for test jitter.
+async fn simulated_delay() {
+    tokio::time::sleep(Duration::from_millis(jitter_ms())).await;
+}
+
+fn jitter_ms() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let seed = COUNTER.fetch_add(1, Ordering::Relaxed) ^ 0x9E3779B97F4A7C15;
+    let mut x = seed.wrapping_add(1);
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    x % 8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn offer_fans_out_to_other_viewers() {
+        let server = TestServer::create("test://offer-fan-out", "test-secret").await;
+        server.create_room("room-1").await.unwrap();
+
+        server.join("room-1", "producer-1", ConnectionRole::Producer).await.unwrap();
+        server.join("room-1", "viewer-1", ConnectionRole::Consumer).await.unwrap();
+
+        server.publish("room-1", "producer-1", "v=0 sdp-offer").await.unwrap();
+
+        let received = server.received("viewer-1").await;
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].message_type, SignalingMessageType::Offer);
+        assert_eq!(
+            received[0].data.as_ref().and_then(|d| d.get("sdp")).and_then(|s| s.as_str()),
+            Some("v=0 sdp-offer")
+        );
+    }
+
+    #[tokio::test]
+    async fn leave_notifies_remaining_participants() {
+        let server = TestServer::create("test://leave-notify", "test-secret").await;
+        server.create_room("room-1").await.unwrap();
+
+        server.join("room-1", "producer-1", ConnectionRole::Producer).await.unwrap();
+        server.join("room-1", "viewer-1", ConnectionRole::Consumer).await.unwrap();
+
+        server.unpublish("room-1", "producer-1").await.unwrap();
+
+        let received = server.received("viewer-1").await;
+        assert!(received.iter().any(|m| m.message_type == SignalingMessageType::Leave));
+    }
+
+    #[tokio::test]
+    async fn registry_looks_up_created_servers() {
+        let server = TestServer::create("test://registry-lookup", "test-secret").await;
+        let found = TestServer::get(server.url()).await;
+        assert!(found.is_some());
+        assert!(TestServer::get("test://does-not-exist").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn join_rejects_a_token_without_the_role_s_required_grant() {
+        let server = TestServer::create("test://join-rejects-missing-grant", "test-secret").await;
+        server.create_room("room-1").await.unwrap();
+
+        // Mint a token scoped to `can_subscribe` only, then try to join as a
+        // `Producer`, which needs `can_publish`.
+        let grants = VideoGrants {
+            room: "room-1".to_string(),
+            room_join: true,
+            can_publish: false,
+            can_subscribe: true,
+        };
+        let token = server.token_verifier.mint("producer-1", grants, None).unwrap();
+        let message = SignalingMessage {
+            message_type: SignalingMessageType::Join,
+            connection_id: Some("producer-1".to_string()),
+            sender_id: None,
+            offer_id: None,
+            data: Some(serde_json::json!({ "role": "producer", "token": token })),
+            is_sender: Some(true),
+        };
+
+        assert!(server.manager.handle_message("room-1", &message).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn join_rejects_a_token_scoped_to_a_different_room() {
+        let server = TestServer::create("test://join-rejects-wrong-room", "test-secret").await;
+        server.create_room("room-1").await.unwrap();
+
+        let grants = VideoGrants {
+            room: "room-2".to_string(),
+            room_join: true,
+            can_publish: false,
+            can_subscribe: true,
+        };
+        let token = server.token_verifier.mint("viewer-1", grants, None).unwrap();
+        let message = SignalingMessage {
+            message_type: SignalingMessageType::Join,
+            connection_id: Some("viewer-1".to_string()),
+            sender_id: None,
+            offer_id: None,
+            data: Some(serde_json::json!({ "role": "consumer", "token": token })),
+            is_sender: Some(false),
+        };
+
+        assert!(server.manager.handle_message("room-1", &message).await.is_err());
+    }
+}
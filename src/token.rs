@@ -0,0 +1,137 @@
+//! JWT-based room access tokens, modeled on LiveKit's `AccessToken` /
+//! `VideoGrants`. A token carries a room name and a set of grants; callers
+//! verify it once on `Join` and thread the decoded grants onward so later
+//! `create_producer` / `create_consumer` calls don't need to re-parse it.
+
+use anyhow::{anyhow, Result};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+
+/// Permissions granted to a token, scoped to a single room.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VideoGrants {
+    pub room: String,
+    #[serde(default)]
+    pub room_join: bool,
+    #[serde(default)]
+    pub can_publish: bool,
+    #[serde(default)]
+    pub can_subscribe: bool,
+}
+
+/// Decoded token claims.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    /// Participant identity.
+    pub sub: String,
+    pub video: VideoGrants,
+    /// Unix timestamp the token expires at, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+}
+
+/// Verifies and mints HS256 tokens against a shared secret.
+#[derive(Clone)]
+pub struct TokenVerifier {
+    secret: String,
+}
+
+impl TokenVerifier {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self { secret: secret.into() }
+    }
+
+    /// Mints a token for the given identity and grants. Intended for test
+    /// setup and for a control plane that has its own copy of the secret.
+    pub fn mint(&self, identity: &str, grants: VideoGrants, ttl_secs: Option<i64>) -> Result<String> {
+        let claims = Claims {
+            sub: identity.to_string(),
+            video: grants,
+            exp: ttl_secs.map(|ttl| now_unix() + ttl),
+        };
+
+        encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(self.secret.as_bytes()),
+        )
+        .map_err(|err| anyhow!("failed to mint token: {err}"))
+    }
+
+    /// Verifies the signature and (if present) expiry of `token`, returning
+    /// the decoded claims.
+    pub fn verify(&self, token: &str) -> Result<Claims> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        // We check `exp` ourselves below so tokens without one still
+        // validate. `validate_exp = false` alone isn't enough: `Validation`
+        // defaults `required_spec_claims` to `{"exp"}` regardless, so a
+        // token minted with `ttl_secs: None` (no `exp` field at all) would
+        // still fail decode with `MissingRequiredClaim` unless we also clear
+        // that set.
+        validation.validate_exp = false;
+        validation.required_spec_claims.clear();
+
+        let data = decode::<Claims>(token, &DecodingKey::from_secret(self.secret.as_bytes()), &validation)
+            .map_err(|err| anyhow!("invalid token: {err}"))?;
+
+        if let Some(exp) = data.claims.exp {
+            if now_unix() >= exp {
+                return Err(anyhow!("token expired"));
+            }
+        }
+
+        Ok(data.claims)
+    }
+}
+
+fn now_unix() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grants(room: &str) -> VideoGrants {
+        VideoGrants { room: room.to_string(), room_join: true, can_publish: true, can_subscribe: true }
+    }
+
+    #[test]
+    fn verifies_a_token_minted_without_a_ttl() {
+        let verifier = TokenVerifier::new("test-secret");
+        let token = verifier.mint("alice", grants("room-1"), None).unwrap();
+
+        let claims = verifier.verify(&token).unwrap();
+        assert_eq!(claims.sub, "alice");
+        assert_eq!(claims.video, grants("room-1"));
+        assert_eq!(claims.exp, None);
+    }
+
+    #[test]
+    fn rejects_an_expired_token() {
+        let verifier = TokenVerifier::new("test-secret");
+        let token = verifier.mint("alice", grants("room-1"), Some(-1)).unwrap();
+
+        assert!(verifier.verify(&token).is_err());
+    }
+
+    #[test]
+    fn accepts_a_token_that_has_not_expired_yet() {
+        let verifier = TokenVerifier::new("test-secret");
+        let token = verifier.mint("alice", grants("room-1"), Some(60)).unwrap();
+
+        assert!(verifier.verify(&token).is_ok());
+    }
+
+    #[test]
+    fn rejects_a_token_signed_with_a_different_secret() {
+        let minted_by = TokenVerifier::new("secret-a");
+        let verified_by = TokenVerifier::new("secret-b");
+        let token = minted_by.mint("alice", grants("room-1"), None).unwrap();
+
+        assert!(verified_by.verify(&token).is_err());
+    }
+}
@@ -0,0 +1,113 @@
+//! WHIP (WebRTC-HTTP Ingestion Protocol) ingest endpoint.
+//!
+//! This gives a camera/encoder a single HTTP POST to publish into a room,
+//! as an alternative to the JSON signaling flow in `SimpleSfuManager::handle_message`.
+//! It mirrors the WHIP client signaller pattern from gst-plugins-rs, but runs
+//! server-side: the offer comes in over HTTP instead of being sent out.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, post},
+    Router,
+};
+use uuid::Uuid;
+
+use crate::sfu::{ConnectionRole, SfuManager};
+
+/// Builds the axum router exposing `POST /whip/{room_id}` and the matching
+/// `DELETE` resource endpoint for tearing a WHIP session back down.
+pub fn router(manager: Arc<SfuManager>) -> Router {
+    Router::new()
+        .route("/whip/:room_id", post(publish))
+        .route("/whip/:room_id/resource/:connection_id", delete(unpublish))
+        .with_state(manager)
+}
+
+async fn publish(
+    State(manager): State<Arc<SfuManager>>,
+    Path(room_id): Path<String>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    match headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()) {
+        Some(ct) if ct.starts_with("application/sdp") => {}
+        _ => return (StatusCode::UNSUPPORTED_MEDIA_TYPE, "expected application/sdp").into_response(),
+    }
+
+    let token = match headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+    {
+        Some(token) => token,
+        None => return (StatusCode::UNAUTHORIZED, "missing Bearer token").into_response(),
+    };
+
+    // A WHIP session is just a sender connection; reuse the room/connection
+    // bookkeeping the JSON signaling path already uses. `create_room` is
+    // idempotent, so a second camera publishing into an already-active room
+    // doesn't reset its existing producer/consumer bookkeeping.
+    if let Err(err) = manager.create_room(room_id.clone()).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response();
+    }
+
+    let connection_id = Uuid::new_v4().to_string();
+    if let Err(err) = manager.add_connection(&room_id, connection_id.clone(), ConnectionRole::Producer, token).await {
+        return (StatusCode::UNAUTHORIZED, err.to_string()).into_response();
+    }
+
+    let transport = match manager.create_transport(connection_id.clone()).await {
+        Ok(transport) => transport,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    };
+
+    let (producer_id, answer_sdp) = match manager
+        .create_producer_from_sdp(&connection_id, &transport, &body)
+        .await
+    {
+        Ok(result) => result,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+
+    tracing::info!(room_id, connection_id, producer_id, "WHIP producer created");
+
+    let location = format!("/whip/{room_id}/resource/{connection_id}");
+
+    (
+        StatusCode::CREATED,
+        [
+            (header::CONTENT_TYPE, "application/sdp".to_string()),
+            (header::LOCATION, location),
+        ],
+        answer_sdp,
+    )
+        .into_response()
+}
+
+async fn unpublish(
+    State(manager): State<Arc<SfuManager>>,
+    Path((room_id, connection_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    let token = match headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+    {
+        Some(token) => token,
+        None => return (StatusCode::UNAUTHORIZED, "missing Bearer token").into_response(),
+    };
+
+    if let Err(err) = manager.authorize_teardown(&room_id, &connection_id, token).await {
+        return (StatusCode::UNAUTHORIZED, err.to_string()).into_response();
+    }
+
+    match manager.remove_connection(&connection_id).await {
+        Ok(()) => StatusCode::OK.into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    }
+}